@@ -0,0 +1,108 @@
+//! A thin wrapper around GitHub's REST API, used to resolve org/team
+//! membership and permissions for the `github:org:team` owner convention.
+//! Requests are authenticated with the calling user's OAuth token and
+//! issued relative to `https://api.github.com`.
+
+use std::cell::RefCell;
+use std::str;
+
+use curl::easy::{Easy, List};
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use app::App;
+use util::{human, CargoResult};
+
+const API_HOST: &str = "https://api.github.com";
+
+thread_local! {
+    // libcurl's `Easy` handle doesn't expose response headers after the
+    // transfer completes, only a handful of fixed `CURLINFO_*` fields like
+    // the status code. Since each request to GitHub is handled
+    // synchronously on a single thread, we stash the `Link` header here
+    // during the transfer and let `link_header` read it back afterwards.
+    static LAST_LINK_HEADER: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// A GitHub OAuth access token, scoped to whichever user it was minted for.
+pub struct Token {
+    access_token: String,
+}
+
+pub fn token(access_token: String) -> Token {
+    Token { access_token }
+}
+
+/// Issues an authenticated `GET` against the GitHub API and returns the
+/// `curl` handle (so callers can inspect the status code or, via
+/// `link_header`, the `Link` header) along with the raw response body.
+pub fn github(app: &App, url: &str, token: &Token) -> CargoResult<(Easy, String)> {
+    let url = if url.starts_with("http") {
+        url.to_string()
+    } else {
+        format!("{}{}", API_HOST, url)
+    };
+
+    let mut headers = List::new();
+    headers.append(&format!("Authorization: token {}", token.access_token))?;
+    headers.append("Accept: application/vnd.github.v3+json")?;
+    headers.append("User-Agent: crates.io")?;
+
+    LAST_LINK_HEADER.with(|cell| *cell.borrow_mut() = None);
+
+    let mut handle = app.handle();
+    handle.url(&url)?;
+    handle.http_headers(headers)?;
+    handle.header_function(|line| {
+        if let Ok(line) = str::from_utf8(line) {
+            if let Some(idx) = line.find(':') {
+                if line[..idx].eq_ignore_ascii_case("link") {
+                    let value = line[idx + 1..].trim().to_string();
+                    LAST_LINK_HEADER.with(|cell| *cell.borrow_mut() = Some(value));
+                }
+            }
+        }
+        true
+    })?;
+
+    let mut data = Vec::new();
+    {
+        let mut transfer = handle.transfer();
+        transfer.write_function(|buf| {
+            data.extend_from_slice(buf);
+            Ok(buf.len())
+        })?;
+        transfer.perform()?;
+    }
+
+    let body =
+        String::from_utf8(data).map_err(|_| human("got a non-utf8 response from GitHub"))?;
+    Ok((handle, body))
+}
+
+/// Deserializes a GitHub API response, mapping non-2xx responses to a
+/// human-readable error.
+pub fn parse_github_response<T: DeserializeOwned>(mut handle: Easy, data: &str) -> CargoResult<T> {
+    let code = handle.response_code()?;
+    if code < 200 || code >= 300 {
+        return Err(human(&format_args!(
+            "didn't get a 200 result from GitHub, got {}: {}",
+            code, data
+        )));
+    }
+
+    serde_json::from_str(data)
+        .map_err(|e| human(&format_args!("error parsing GitHub response: {}", e)))
+}
+
+/// Returns the raw `Link` response header from the most recently completed
+/// `github()` call on this thread, if GitHub sent one.
+pub fn link_header(_handle: &Easy) -> Option<String> {
+    LAST_LINK_HEADER.with(|cell| cell.borrow().clone())
+}
+
+/// Strips the `https://api.github.com` prefix from a GitHub API URL so it
+/// can be handed straight back to `github()`, which re-adds it.
+pub fn strip_api_host(url: &str) -> String {
+    url.trim_start_matches(API_HOST).to_string()
+}
@@ -0,0 +1,106 @@
+//! HTTP handlers for `PUT /api/v1/crates/:crate_id/owners` and the
+//! `crate_owner_invitations` endpoints. These are the real call sites for
+//! `owner::add_owner`, `owner::invitations_for_user`, and
+//! `owner::respond_to_invitation` — nothing should insert into
+//! `crate_owners` or `users` directly from here. Requires `mod owners;` in
+//! `src/krate.rs` and the matching `router.put(...)`/`router.get(...)`
+//! entries alongside the other crate routes.
+
+use std::io::Read;
+
+use conduit::{Request, Response};
+use diesel::prelude::*;
+
+use db::RequestTransaction;
+use owner::{self, EncodableCrateOwnerInvitation, OwnerRole};
+use util::{human, CargoResult, RequestUtils};
+use Crate;
+
+#[derive(Deserialize)]
+struct OwnerChangeRequest {
+    owners: Vec<String>,
+}
+
+/// `PUT /api/v1/crates/:crate_id/owners`
+///
+/// Invites each named login as an owner of the crate through
+/// `owner::add_owner`. Team logins (`github:org:team`) are added
+/// immediately; user logins get a `CrateOwnerInvitation` instead of
+/// ownership outright.
+pub fn add_owners(req: &mut Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let request: OwnerChangeRequest =
+        serde_json::from_str(&body).map_err(|_| human("invalid json request"))?;
+
+    let crate_name = &req.params()["crate_id"];
+    let conn = req.db_conn()?;
+    let req_user = req.user()?;
+    let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
+
+    for login in &request.owners {
+        owner::add_owner(req.app(), &conn, req_user, &krate, login, OwnerRole::Owner)?;
+    }
+
+    #[derive(Serialize)]
+    struct R {
+        ok: bool,
+        msg: String,
+    }
+    Ok(req.json(&R {
+        ok: true,
+        msg: "owner invitation(s) sent".to_string(),
+    }))
+}
+
+/// `GET /api/v1/me/crate_owner_invitations`
+///
+/// Lists the invitations pending for the authenticated user.
+pub fn list_invitations(req: &mut Request) -> CargoResult<Response> {
+    let conn = req.db_conn()?;
+    let user = req.user()?;
+    let invitations = owner::invitations_for_user(user, &conn)?
+        .into_iter()
+        .map(|invitation| invitation.encodable())
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        crate_owner_invitations: Vec<EncodableCrateOwnerInvitation>,
+    }
+    Ok(req.json(&R {
+        crate_owner_invitations: invitations,
+    }))
+}
+
+#[derive(Deserialize, Serialize)]
+struct InvitationResponse {
+    accept: bool,
+}
+
+/// `PUT /api/v1/me/crate_owner_invitations/:crate_id`
+///
+/// Accepts or declines a pending invitation on behalf of the authenticated
+/// user, per the `accept` field in the request body.
+pub fn handle_invite(req: &mut Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let request: InvitationResponse =
+        serde_json::from_str(&body).map_err(|_| human("invalid json request"))?;
+
+    let crate_id = req.params()["crate_id"]
+        .parse::<i32>()
+        .map_err(|_| human("invalid crate_id"))?;
+    let conn = req.db_conn()?;
+    let user = req.user()?;
+
+    owner::respond_to_invitation(&conn, user, crate_id, request.accept)?;
+
+    #[derive(Serialize)]
+    struct R {
+        crate_owner_invitation: InvitationResponse,
+    }
+    Ok(req.json(&R {
+        crate_owner_invitation: request,
+    }))
+}
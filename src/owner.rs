@@ -1,3 +1,7 @@
+use std::cmp;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use curl::easy::Easy;
 use diesel::prelude::*;
 
 use app::App;
@@ -17,6 +21,7 @@ pub struct CrateOwner {
     pub owner_id: i32,
     pub created_by: i32,
     pub owner_kind: i32,
+    pub role: i32,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -26,10 +31,198 @@ pub enum OwnerKind {
     Team = 1,
 }
 
+/// The level of access a user owner has been granted on a crate. Lets us
+/// hand out a publish-only "maintainer" role without also granting the
+/// ability to add or remove other owners.
+///
+/// NOTE: the order of these variants matters, same as `Rights` below —
+/// the derived `Ord` relies on `User` sorting lowest and `Owner` highest.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[repr(i32)]
+pub enum OwnerRole {
+    /// Can publish new versions, but cannot manage ownership.
+    User = 0,
+    Manager = 1,
+    Admin = 2,
+    /// Full control, including adding and removing other owners.
+    Owner = 3,
+}
+
+impl OwnerRole {
+    /// Maps a role onto the coarser `Rights` an owner check cares about.
+    fn rights(self) -> Rights {
+        match self {
+            OwnerRole::Owner => Rights::Full,
+            OwnerRole::Admin | OwnerRole::Manager | OwnerRole::User => Rights::Publish,
+        }
+    }
+}
+
+impl From<i32> for OwnerRole {
+    fn from(role: i32) -> Self {
+        match role {
+            3 => OwnerRole::Owner,
+            2 => OwnerRole::Admin,
+            1 => OwnerRole::Manager,
+            _ => OwnerRole::User,
+        }
+    }
+}
+
+/// A pending invitation for a user to become an owner of a crate.
+///
+/// User owners are never written straight into `crate_owners` — anyone with
+/// publish rights could otherwise make someone else an owner without their
+/// consent. So a user has to accept an invitation first. Team owners skip
+/// this, since membership is already verified against GitHub at
+/// `Team::create_or_update` time.
+#[derive(Queryable, Identifiable, Associations, Debug, Clone, Copy)]
+#[belongs_to(Crate)]
+#[table_name = "crate_owner_invitations"]
+#[primary_key(invited_user_id, crate_id)]
+pub struct CrateOwnerInvitation {
+    pub invited_user_id: i32,
+    pub invited_by_user_id: i32,
+    pub crate_id: i32,
+    pub created_at: NaiveDateTime,
+    /// The role the invited user will be granted if they accept.
+    pub role: i32,
+}
+
+#[derive(Insertable, AsChangeset, Debug, Clone, Copy)]
+#[table_name = "crate_owner_invitations"]
+pub struct NewCrateOwnerInvitation {
+    pub invited_user_id: i32,
+    pub invited_by_user_id: i32,
+    pub crate_id: i32,
+    pub role: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncodableCrateOwnerInvitation {
+    pub crate_id: i32,
+    pub invited_by_user_id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+impl CrateOwner {
+    /// Looks up the access level stored for a specific owner of a crate.
+    fn role(
+        conn: &PgConnection,
+        crate_id_: i32,
+        owner_id_: i32,
+        kind: OwnerKind,
+    ) -> CargoResult<OwnerRole> {
+        use schema::crate_owners::dsl::*;
+
+        let stored: i32 = crate_owners
+            .filter(crate_id.eq(crate_id_))
+            .filter(owner_id.eq(owner_id_))
+            .filter(owner_kind.eq(kind as i32))
+            .select(role)
+            .first(conn)?;
+
+        Ok(OwnerRole::from(stored))
+    }
+}
+
+impl NewCrateOwnerInvitation {
+    /// Records an invitation, replacing any existing one for the same
+    /// crate/user pair (e.g. if it was invited, removed, and invited again).
+    pub fn create_or_update(&self, conn: &PgConnection) -> QueryResult<CrateOwnerInvitation> {
+        use diesel::insert_into;
+        use schema::crate_owner_invitations::dsl::*;
+
+        insert_into(crate_owner_invitations)
+            .values(self)
+            .on_conflict((invited_user_id, crate_id))
+            .do_update()
+            .set(self)
+            .get_result(conn)
+    }
+}
+
+impl CrateOwnerInvitation {
+    pub fn find_by_id(crate_id_: i32, user_id: i32, conn: &PgConnection) -> CargoResult<Self> {
+        use schema::crate_owner_invitations::dsl::*;
+
+        crate_owner_invitations
+            .find((user_id, crate_id_))
+            .first(conn)
+            .map_err(|_| human("no invitation found"))
+    }
+
+    /// Lists every invitation pending for a given user, across all crates.
+    pub fn invitations_for_user(user_id: i32, conn: &PgConnection) -> CargoResult<Vec<Self>> {
+        use schema::crate_owner_invitations::dsl::*;
+
+        crate_owner_invitations
+            .filter(invited_user_id.eq(user_id))
+            .load(conn)
+            .map_err(Into::into)
+    }
+
+    /// Turns the invitation into real ownership: inserts the `crate_owners`
+    /// row and removes the invitation, all in one transaction.
+    pub fn accept(&self, conn: &PgConnection) -> CargoResult<()> {
+        use diesel::insert_into;
+
+        conn.transaction(|| {
+            insert_into(crate_owners::table)
+                .values(&CrateOwner {
+                    crate_id: self.crate_id,
+                    owner_id: self.invited_user_id,
+                    created_by: self.invited_by_user_id,
+                    owner_kind: OwnerKind::User as i32,
+                    role: self.role,
+                })
+                .on_conflict(crate_owners::table.primary_key())
+                .do_update()
+                .set((
+                    crate_owners::deleted.eq(false),
+                    crate_owners::role.eq(self.role),
+                    crate_owners::created_by.eq(self.invited_by_user_id),
+                ))
+                .execute(conn)?;
+
+            self.decline(conn)
+        })
+    }
+
+    /// Declining just throws the invitation away; the user never becomes an
+    /// owner.
+    pub fn decline(&self, conn: &PgConnection) -> CargoResult<()> {
+        use schema::crate_owner_invitations::dsl::*;
+
+        diesel::delete(
+            crate_owner_invitations
+                .filter(invited_user_id.eq(self.invited_user_id))
+                .filter(crate_id.eq(self.crate_id)),
+        )
+        .execute(conn)?;
+
+        Ok(())
+    }
+
+    pub fn encodable(self) -> EncodableCrateOwnerInvitation {
+        EncodableCrateOwnerInvitation {
+            crate_id: self.crate_id,
+            invited_by_user_id: self.invited_by_user_id,
+            created_at: self.created_at,
+        }
+    }
+}
+
 /// Unifies the notion of a User or a Team.
+///
+/// A user owner carries the `OwnerRole` it was looked up with, if any, so
+/// that `rights()` can reuse the role `Owner::owning` already joined in
+/// rather than re-querying `crate_owners` for the same row. It's `None`
+/// when the `Owner` didn't come from a `crate_owners` row in the first
+/// place (e.g. `find_or_create_by_login` resolving a not-yet-owner login).
 #[derive(Debug)]
 pub enum Owner {
-    User(User),
+    User(User, Option<OwnerRole>),
     Team(Team),
 }
 
@@ -50,6 +243,11 @@ pub struct Team {
     /// Sugary goodness
     pub name: Option<String>,
     pub avatar: Option<String>,
+    /// The repo permission (e.g. "pull", "triage", "push", "maintain", or
+    /// "admin") GitHub reports for this team, captured so crate rights
+    /// reflect the underlying GitHub team configuration instead of treating
+    /// all membership as equal.
+    pub permission: String,
 }
 
 #[derive(Serialize, Debug)]
@@ -59,6 +257,7 @@ pub struct EncodableTeam {
     pub name: Option<String>,
     pub avatar: Option<String>,
     pub url: Option<String>,
+    pub permission: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -87,6 +286,7 @@ pub struct NewTeam<'a> {
     pub github_id: i32,
     pub name: Option<String>,
     pub avatar: Option<String>,
+    pub permission: String,
 }
 
 impl<'a> NewTeam<'a> {
@@ -95,12 +295,14 @@ impl<'a> NewTeam<'a> {
         github_id: i32,
         name: Option<String>,
         avatar: Option<String>,
+        permission: String,
     ) -> Self {
         NewTeam {
             login: login,
             github_id: github_id,
             name: name,
             avatar: avatar,
+            permission: permission,
         }
     }
 
@@ -117,47 +319,59 @@ impl<'a> NewTeam<'a> {
     }
 }
 
-impl Team {
-    /// Tries to create the Team in the DB (assumes a `:` has already been found).
-    pub fn create_or_update(
+/// A pluggable organization/team backend, keyed off the first `:`-delimited
+/// segment of a team login (the same `{provider}:{scope}:{id}` convention
+/// used to identify users across providers). Registering a new provider here
+/// is what it takes to support e.g. `gitlab:group:team` without touching the
+/// owner/rights core.
+pub trait TeamProvider {
+    /// Resolves `org:team` against the provider and returns the data to
+    /// persist. Does not touch the database itself.
+    fn resolve_team<'a>(
+        &self,
         app: &App,
-        conn: &PgConnection,
-        login: &str,
+        login: &'a str,
+        org: &str,
+        team: &str,
         req_user: &User,
-    ) -> CargoResult<Self> {
-        // must look like system:xxxxxxx
-        let mut chunks = login.split(':');
-        match chunks.next().unwrap() {
-            // github:rust-lang:owners
-            "github" => {
-                // Ok to unwrap since we know one ":" is contained
-                let org = chunks.next().unwrap();
-                let team = chunks.next().ok_or_else(|| {
-                    human(
-                        "missing github team argument; \
-                         format is github:org:team",
-                    )
-                })?;
-                Team::create_or_update_github_team(app, conn, login, org, team, req_user)
-            }
-            _ => Err(human(
-                "unknown organization handler, \
-                 only 'github:org:team' is supported",
-            )),
-        }
+    ) -> CargoResult<NewTeam<'a>>;
+
+    /// Checks whether `user` is currently a member of `team`.
+    fn contains_user(
+        &self,
+        app: &App,
+        conn: &PgConnection,
+        team: &Team,
+        user: &User,
+    ) -> CargoResult<bool>;
+}
+
+/// Looks up the `TeamProvider` registered for the prefix before the first
+/// `:` in a team login, e.g. `"github"` in `github:rust-lang:owners`.
+fn team_provider(key: &str) -> CargoResult<&'static TeamProvider> {
+    match key {
+        "github" => Ok(&GithubTeamProvider),
+        _ => Err(human(
+            "unknown organization handler, \
+             only 'github:org:team' is supported",
+        )),
     }
+}
 
+struct GithubTeamProvider;
+
+impl TeamProvider for GithubTeamProvider {
     /// Tries to create or update a Github Team. Assumes `org` and `team` are
-    /// correctly parsed out of the full `name`. `name` is passed as a
-    /// convenience to avoid rebuilding it.
-    fn create_or_update_github_team(
+    /// correctly parsed out of the full `login`, which is passed through as
+    /// a convenience to avoid rebuilding it.
+    fn resolve_team<'a>(
+        &self,
         app: &App,
-        conn: &PgConnection,
-        login: &str,
+        login: &'a str,
         org_name: &str,
         team_name: &str,
         req_user: &User,
-    ) -> CargoResult<Self> {
+    ) -> CargoResult<NewTeam<'a>> {
         // GET orgs/:org/teams
         // check that `team` is the `slug` in results, and grab its data
 
@@ -177,29 +391,8 @@ impl Team {
             )));
         }
 
-        #[derive(Deserialize)]
-        struct GithubTeam {
-            slug: String,         // the name we want to find
-            id: i32,              // unique GH id (needed for membership queries)
-            name: Option<String>, // Pretty name
-        }
-
-        // FIXME: we just set per_page=100 and don't bother chasing pagination
-        // links. A hundred teams should be enough for any org, right?
-        let url = format!("/orgs/{}/teams?per_page=100", org_name);
         let token = github::token(req_user.gh_access_token.clone());
-        let (handle, data) = github::github(app, &url, &token)?;
-        let teams: Vec<GithubTeam> = github::parse_github_response(handle, &data)?;
-
-        let team = teams
-            .into_iter()
-            .find(|team| team.slug == team_name)
-            .ok_or_else(|| {
-                human(&format_args!(
-                    "could not find the github team {}/{}",
-                    org_name, team_name
-                ))
-            })?;
+        let team = find_github_team(app, &token, org_name, team_name)?;
 
         if !team_with_gh_id_contains_user(app, team.id, req_user)? {
             return Err(human("only members of a team can add it as an owner"));
@@ -214,17 +407,60 @@ impl Team {
         let (handle, resp) = github::github(app, &url, &token)?;
         let org: Org = github::parse_github_response(handle, &resp)?;
 
-        NewTeam::new(login, team.id, team.name, org.avatar_url)
+        Ok(NewTeam::new(
+            login,
+            team.id,
+            team.name,
+            org.avatar_url,
+            team.permission,
+        ))
+    }
+
+    /// Asks Github (via the `team_memberships` cache) if this User is a
+    /// member of the given team. Note that we're assuming that the given
+    /// user is the one interested in the answer. If this is not the case,
+    /// then we could accidentally leak private membership information here.
+    fn contains_user(
+        &self,
+        app: &App,
+        conn: &PgConnection,
+        team: &Team,
+        user: &User,
+    ) -> CargoResult<bool> {
+        team_membership_contains_user(app, conn, team, user)
+    }
+}
+
+impl Team {
+    /// Tries to create the Team in the DB (assumes a `:` has already been found).
+    pub fn create_or_update(
+        app: &App,
+        conn: &PgConnection,
+        login: &str,
+        req_user: &User,
+    ) -> CargoResult<Self> {
+        // must look like system:org:team
+        let mut chunks = login.split(':');
+        let provider = team_provider(chunks.next().unwrap())?;
+        // Ok to unwrap since we know one ":" is contained
+        let org = chunks.next().unwrap();
+        let team = chunks.next().ok_or_else(|| {
+            human(
+                "missing github team argument; \
+                 format is github:org:team",
+            )
+        })?;
+
+        provider
+            .resolve_team(app, login, org, team, req_user)?
             .create_or_update(conn)
             .map_err(Into::into)
     }
 
-    /// Phones home to Github to ask if this User is a member of the given team.
-    /// Note that we're assuming that the given user is the one interested in
-    /// the answer. If this is not the case, then we could accidentally leak
-    /// private membership information here.
-    pub fn contains_user(&self, app: &App, user: &User) -> CargoResult<bool> {
-        team_with_gh_id_contains_user(app, self.github_id, user)
+    /// Asks the team's provider whether this User is a member.
+    pub fn contains_user(&self, app: &App, conn: &PgConnection, user: &User) -> CargoResult<bool> {
+        let provider_key = self.login.split(':').next().unwrap_or_default();
+        team_provider(provider_key)?.contains_user(app, conn, self, user)
     }
 
     pub fn owning(krate: &Crate, conn: &PgConnection) -> CargoResult<Vec<Owner>> {
@@ -246,6 +482,7 @@ impl Team {
             name,
             login,
             avatar,
+            permission,
             ..
         } = self;
         let url = Team::github_url(&login);
@@ -256,6 +493,7 @@ impl Team {
             name: name,
             avatar: avatar,
             url: Some(url),
+            permission: permission,
         }
     }
 
@@ -268,6 +506,86 @@ impl Team {
             login_pieces.next().expect("org failed"),
         )
     }
+
+    /// A team without write access to the GitHub repo (e.g. `pull` or
+    /// `triage`) shouldn't be able to publish new crate versions just
+    /// because its members are listed as owners. Allowlisted rather than
+    /// blocklisted since GitHub's fine-grained repo roles keep growing
+    /// (`triage`, `maintain`, ...) and new values should default to no
+    /// publish access.
+    fn grants_publish(&self) -> bool {
+        match self.permission.as_str() {
+            "push" | "maintain" | "admin" => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GithubTeam {
+    slug: String,         // the name we want to find
+    id: i32,              // unique GH id (needed for membership queries)
+    name: Option<String>, // Pretty name
+    permission: String,   // e.g. "pull", "triage", "push", "maintain", or "admin"
+}
+
+/// Pages through `GET /orgs/:org/teams` until a team whose `slug` matches
+/// `team_name` turns up, following the `Link: rel="next"` header so orgs
+/// with more than one page of teams still resolve correctly.
+fn find_github_team(
+    app: &App,
+    token: &github::Token,
+    org_name: &str,
+    team_name: &str,
+) -> CargoResult<GithubTeam> {
+    let mut url = format!("/orgs/{}/teams?per_page=100", org_name);
+
+    loop {
+        let (handle, data) = github::github(app, &url, token)?;
+        let next = next_page_url(&handle);
+        let teams: Vec<GithubTeam> = github::parse_github_response(handle, &data)?;
+
+        if let Some(team) = teams.into_iter().find(|team| team.slug == team_name) {
+            return Ok(team);
+        }
+
+        match next {
+            Some(next_url) => url = next_url,
+            None => {
+                return Err(human(&format_args!(
+                    "could not find the github team {}/{}",
+                    org_name, team_name
+                )))
+            }
+        }
+    }
+}
+
+/// Follows the `Link` response header (RFC 5988) to the `rel="next"` page,
+/// e.g. `<https://api.github.com/organizations/1/team?page=2>; rel="next"`,
+/// with the API host prefix stripped so it can be handed straight back to
+/// `github::github`.
+fn next_page_url(handle: &Easy) -> Option<String> {
+    next_url_from_link_header(&github::link_header(handle)?)
+}
+
+/// Parses a raw `Link` response header and returns the `rel="next"` URL,
+/// with the API host prefix stripped, if present. Split out from
+/// `next_page_url` so the parsing itself can be unit tested without a live
+/// `Easy` handle.
+fn next_url_from_link_header(link: &str) -> Option<String> {
+    link.split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let url = parts.next()?.trim();
+            let is_next = parts.any(|param| param.trim() == r#"rel="next""#);
+            if !is_next {
+                return None;
+            }
+            let url = url.trim_start_matches('<').trim_end_matches('>');
+            Some(github::strip_api_host(url))
+        })
+        .next()
 }
 
 fn team_with_gh_id_contains_user(app: &App, github_id: i32, user: &User) -> CargoResult<bool> {
@@ -295,6 +613,102 @@ fn team_with_gh_id_contains_user(app: &App, github_id: i32, user: &User) -> Carg
     Ok(membership.state == "active")
 }
 
+/// How long a cached `team_memberships` row is trusted before we phone
+/// GitHub again.
+const MEMBERSHIP_FRESHNESS_MINUTES: i64 = 5;
+
+/// How long a cached `team_memberships` row may still be used as a fallback
+/// when GitHub itself is erroring. Looser than `MEMBERSHIP_FRESHNESS_MINUTES`
+/// so a brief GitHub outage doesn't fail publishing, but still bounded so a
+/// membership that was revoked long ago can't keep granting publish rights
+/// forever just because GitHub is down.
+const MEMBERSHIP_FALLBACK_MAX_AGE_MINUTES: i64 = 60;
+
+/// Consults the `team_memberships` cache before hitting GitHub, so a crate
+/// owned by several teams (or a hot publish path) doesn't burn through
+/// GitHub's rate limit on every `rights()` check. Only ever caches the
+/// requesting user's own membership, keeping the privacy note on
+/// `GithubTeamProvider::contains_user` intact.
+fn team_membership_contains_user(
+    app: &App,
+    conn: &PgConnection,
+    team: &Team,
+    user: &User,
+) -> CargoResult<bool> {
+    use schema::team_memberships::dsl::*;
+
+    let cached: Option<(bool, NaiveDateTime)> = team_memberships
+        .filter(team_id.eq(team.id))
+        .filter(user_id.eq(user.id))
+        .select((active, checked_at))
+        .first(conn)
+        .optional()?;
+
+    if let Some((cached_active, cached_at)) = cached {
+        if is_fresh_enough(
+            cached_at,
+            Utc::now().naive_utc(),
+            MEMBERSHIP_FRESHNESS_MINUTES,
+        ) {
+            return Ok(cached_active);
+        }
+    }
+
+    match team_with_gh_id_contains_user(app, team.github_id, user) {
+        Ok(is_active) => {
+            upsert_team_membership(conn, team.id, user.id, is_active)?;
+            Ok(is_active)
+        }
+        // GitHub is unavailable; fall back to the last known-good result
+        // rather than failing the whole `rights()` computation, as long as
+        // it isn't so old it's more likely to be wrong than right.
+        Err(e) => match cached {
+            Some((cached_active, cached_at))
+                if is_fresh_enough(
+                    cached_at,
+                    Utc::now().naive_utc(),
+                    MEMBERSHIP_FALLBACK_MAX_AGE_MINUTES,
+                ) =>
+            {
+                Ok(cached_active)
+            }
+            _ => Err(e),
+        },
+    }
+}
+
+/// Whether a `team_memberships` row checked at `checked_at` is still usable
+/// as of `now`, given a `max_age_minutes` window. Split out from
+/// `team_membership_contains_user` so the freshness and stale-fallback
+/// comparisons can be unit tested without a live `PgConnection`.
+fn is_fresh_enough(checked_at: NaiveDateTime, now: NaiveDateTime, max_age_minutes: i64) -> bool {
+    now.signed_duration_since(checked_at) < Duration::minutes(max_age_minutes)
+}
+
+fn upsert_team_membership(
+    conn: &PgConnection,
+    team_id_: i32,
+    user_id_: i32,
+    is_active: bool,
+) -> QueryResult<()> {
+    use diesel::insert_into;
+    use schema::team_memberships::dsl::*;
+
+    insert_into(team_memberships)
+        .values((
+            team_id.eq(team_id_),
+            user_id.eq(user_id_),
+            active.eq(is_active),
+            checked_at.eq(diesel::dsl::now),
+        ))
+        .on_conflict((team_id, user_id))
+        .do_update()
+        .set((active.eq(is_active), checked_at.eq(diesel::dsl::now)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
 impl Owner {
     /// Finds the owner by name. Always recreates teams to get the most
     /// up-to-date GitHub ID. Fails out if the user isn't found in the
@@ -310,50 +724,68 @@ impl Owner {
     ) -> CargoResult<Owner> {
         if name.contains(':') {
             Ok(Owner::Team(Team::create_or_update(
-                app,
-                conn,
-                name,
-                req_user,
+                app, conn, name, req_user,
             )?))
         } else {
             users::table
                 .filter(users::gh_login.eq(name))
                 .first(conn)
-                .map(Owner::User)
+                .map(|user| Owner::User(user, None))
                 .map_err(|_| human(&format_args!("could not find user with login `{}`", name)))
         }
     }
 
+    /// All current owners (users and teams) of a crate.
+    ///
+    /// Selects `crate_owners::role` alongside the joined user row and
+    /// carries it on `Owner::User` so `rights()` can reuse it instead of
+    /// re-querying `crate_owners` for the same row on the publish hot path.
+    pub fn owning(krate: &Crate, conn: &PgConnection) -> CargoResult<Vec<Owner>> {
+        let base_query = CrateOwner::belonging_to(krate).filter(crate_owners::deleted.eq(false));
+        let users = base_query
+            .inner_join(users::table)
+            .select((users::all_columns, crate_owners::role))
+            .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+            .load::<(User, i32)>(conn)?
+            .into_iter()
+            .map(|(user, role)| Owner::User(user, Some(OwnerRole::from(role))));
+
+        Ok(users.chain(Team::owning(krate, conn)?).collect())
+    }
+
     pub fn kind(&self) -> i32 {
         match *self {
-            Owner::User(_) => OwnerKind::User as i32,
+            Owner::User(..) => OwnerKind::User as i32,
             Owner::Team(_) => OwnerKind::Team as i32,
         }
     }
 
     pub fn login(&self) -> &str {
         match *self {
-            Owner::User(ref user) => &user.gh_login,
+            Owner::User(ref user, _) => &user.gh_login,
             Owner::Team(ref team) => &team.login,
         }
     }
 
     pub fn id(&self) -> i32 {
         match *self {
-            Owner::User(ref user) => user.id,
+            Owner::User(ref user, _) => user.id,
             Owner::Team(ref team) => team.id,
         }
     }
 
     pub fn encodable(self) -> EncodableOwner {
         match self {
-            Owner::User(User {
-                id,
-                name,
-                gh_login,
-                gh_avatar,
-                ..
-            }) => {
+            Owner::User(
+                User {
+                    id,
+                    name,
+                    gh_login,
+                    gh_avatar,
+                    ..
+                },
+                _,
+            ) => {
                 let url = format!("https://github.com/{}", gh_login);
                 EncodableOwner {
                     id: id,
@@ -393,17 +825,239 @@ impl Owner {
 /// `Publish` as well, but this is a non-obvious invariant so we don't bother.
 /// Sweet free optimization if teams are proving burdensome to check.
 /// More than one team isn't really expected, though.
-pub fn rights(app: &App, owners: &[Owner], user: &User) -> CargoResult<Rights> {
+pub fn rights(
+    app: &App,
+    conn: &PgConnection,
+    krate: &Crate,
+    owners: &[Owner],
+    user: &User,
+) -> CargoResult<Rights> {
     let mut best = Rights::None;
     for owner in owners {
         match *owner {
-            Owner::User(ref other_user) => if other_user.id == user.id {
-                return Ok(Rights::Full);
-            },
-            Owner::Team(ref team) => if team.contains_user(app, user)? {
-                best = Rights::Publish;
-            },
+            Owner::User(ref other_user, role) => {
+                if other_user.id == user.id {
+                    // `Owner::owning` already joined `crate_owners::role` for
+                    // this row; only fall back to a fresh query for owners
+                    // that didn't come from that join.
+                    let role = match role {
+                        Some(role) => role,
+                        None => CrateOwner::role(conn, krate.id, other_user.id, OwnerKind::User)?,
+                    };
+                    best = role.rights();
+                    if best == Rights::Full {
+                        return Ok(Rights::Full);
+                    }
+                }
+            }
+            Owner::Team(ref team) => {
+                if team.grants_publish() && team.contains_user(app, conn, user)? {
+                    // Team rows are always written with `OwnerRole::Owner`
+                    // today (see `add_owner`), but rights are still capped
+                    // by the stored role rather than assumed, so a future
+                    // lower team role takes effect without another change
+                    // here.
+                    let role = CrateOwner::role(conn, krate.id, team.id, OwnerKind::Team)?;
+                    best = cmp::min(Rights::Publish, role.rights());
+                }
+            }
         }
     }
     Ok(best)
 }
+
+/// Adds `login` as an owner of `krate` on behalf of `req_user`. This is the
+/// single place that decides how a new owner gets added, so it's the thing
+/// a `PUT /api/v1/crates/:crate/owners` handler should call rather than
+/// inserting into `crate_owners` directly.
+///
+/// Team owners are trusted immediately, since GitHub membership already
+/// vouches for them. User owners instead get a `CrateOwnerInvitation` and
+/// only become owners once they accept it, closing the hole where you could
+/// be made an owner without ever agreeing to it.
+pub fn add_owner(
+    app: &App,
+    conn: &PgConnection,
+    req_user: &User,
+    krate: &Crate,
+    login: &str,
+    role: OwnerRole,
+) -> CargoResult<()> {
+    let owners = Owner::owning(krate, conn)?;
+    match rights(app, conn, krate, &owners, req_user)? {
+        Rights::Full => {}
+        Rights::Publish | Rights::None => {
+            return Err(human(
+                "only owners have permission to invite another user to be an owner",
+            ))
+        }
+    }
+
+    match Owner::find_or_create_by_login(app, conn, req_user, login)? {
+        Owner::User(user, _) => {
+            NewCrateOwnerInvitation {
+                invited_user_id: user.id,
+                invited_by_user_id: req_user.id,
+                crate_id: krate.id,
+                role: role as i32,
+            }
+            .create_or_update(conn)?;
+        }
+        Owner::Team(team) => {
+            use diesel::insert_into;
+
+            insert_into(crate_owners::table)
+                .values(&CrateOwner {
+                    crate_id: krate.id,
+                    owner_id: team.id,
+                    created_by: req_user.id,
+                    owner_kind: OwnerKind::Team as i32,
+                    role: OwnerRole::Owner as i32,
+                })
+                .on_conflict(crate_owners::table.primary_key())
+                .do_update()
+                .set((
+                    crate_owners::deleted.eq(false),
+                    crate_owners::role.eq(OwnerRole::Owner as i32),
+                    crate_owners::created_by.eq(req_user.id),
+                ))
+                .execute(conn)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Every invitation pending for a user, across all crates.
+///
+/// This is what a `GET /api/v1/me/crate_owner_invitations` handler calls.
+pub fn invitations_for_user(
+    user: &User,
+    conn: &PgConnection,
+) -> CargoResult<Vec<CrateOwnerInvitation>> {
+    CrateOwnerInvitation::invitations_for_user(user.id, conn)
+}
+
+/// Accepts or declines a pending invitation on behalf of the invited user.
+///
+/// This is what a `PUT /api/v1/me/crate_owner_invitations/:crate_id` handler
+/// calls, with `accept` coming from the request body.
+pub fn respond_to_invitation(
+    conn: &PgConnection,
+    user: &User,
+    crate_id: i32,
+    accept: bool,
+) -> CargoResult<()> {
+    let invitation = CrateOwnerInvitation::find_by_id(crate_id, user.id, conn)?;
+    if accept {
+        invitation.accept(conn)
+    } else {
+        invitation.decline(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owner_role_rights_boundary() {
+        assert_eq!(OwnerRole::User.rights(), Rights::Publish);
+        assert_eq!(OwnerRole::Manager.rights(), Rights::Publish);
+        assert_eq!(OwnerRole::Admin.rights(), Rights::Publish);
+        assert_eq!(OwnerRole::Owner.rights(), Rights::Full);
+    }
+
+    #[test]
+    fn owner_role_ordering() {
+        assert!(OwnerRole::User < OwnerRole::Manager);
+        assert!(OwnerRole::Manager < OwnerRole::Admin);
+        assert!(OwnerRole::Admin < OwnerRole::Owner);
+    }
+
+    #[test]
+    fn team_provider_resolves_known_prefix() {
+        let provider = team_provider("github").unwrap();
+        assert_eq!(
+            provider as *const _ as *const (),
+            &GithubTeamProvider as *const _ as *const ()
+        );
+    }
+
+    #[test]
+    fn team_provider_rejects_unknown_prefix() {
+        let err = team_provider("gitlab").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("only 'github:org:team' is supported")
+        );
+    }
+
+    fn team_with_permission(permission: &str) -> Team {
+        Team {
+            id: 1,
+            login: "github:rust-lang:owners".to_string(),
+            github_id: 1,
+            name: None,
+            avatar: None,
+            permission: permission.to_string(),
+        }
+    }
+
+    #[test]
+    fn grants_publish_allowlists_write_permissions() {
+        assert!(!team_with_permission("pull").grants_publish());
+        assert!(!team_with_permission("triage").grants_publish());
+        assert!(team_with_permission("push").grants_publish());
+        assert!(team_with_permission("maintain").grants_publish());
+        assert!(team_with_permission("admin").grants_publish());
+    }
+
+    #[test]
+    fn next_url_from_link_header_picks_rel_next() {
+        let link = concat!(
+            r#"<https://api.github.com/organizations/1/team/2/members?page=2>; rel="next", "#,
+            r#"<https://api.github.com/organizations/1/team/2/members?page=4>; rel="last""#
+        );
+        assert_eq!(
+            next_url_from_link_header(link),
+            Some("/organizations/1/team/2/members?page=2".to_string())
+        );
+    }
+
+    #[test]
+    fn next_url_from_link_header_no_next_rel() {
+        let link = r#"<https://api.github.com/organizations/1/team/2/members?page=1>; rel="prev""#;
+        assert_eq!(next_url_from_link_header(link), None);
+    }
+
+    #[test]
+    fn next_url_from_link_header_empty() {
+        assert_eq!(next_url_from_link_header(""), None);
+    }
+
+    #[test]
+    fn is_fresh_enough_within_window() {
+        let checked_at = NaiveDateTime::from_timestamp(1_000, 0);
+        let now = checked_at + Duration::minutes(4);
+        assert!(is_fresh_enough(checked_at, now, 5));
+    }
+
+    #[test]
+    fn is_fresh_enough_outside_window() {
+        let checked_at = NaiveDateTime::from_timestamp(1_000, 0);
+        let now = checked_at + Duration::minutes(5);
+        assert!(!is_fresh_enough(checked_at, now, 5));
+    }
+
+    #[test]
+    fn is_fresh_enough_long_outage_beyond_fallback_window() {
+        let checked_at = NaiveDateTime::from_timestamp(1_000, 0);
+        let now = checked_at + Duration::minutes(MEMBERSHIP_FALLBACK_MAX_AGE_MINUTES + 1);
+        assert!(!is_fresh_enough(
+            checked_at,
+            now,
+            MEMBERSHIP_FALLBACK_MAX_AGE_MINUTES
+        ));
+    }
+}